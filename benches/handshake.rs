@@ -0,0 +1,180 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mmo_rs::auth::{
+    decode_with, AuthClient, AuthClientCrypt, AuthClientSenderImpl, UnknownOpcodePolicy,
+    WriteClose, INIT_KEY,
+};
+use openssl::rsa::{Padding, Rsa};
+use std::io::{Cursor, Result, Write};
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONCURRENCY: &[usize] = &[1, 4, 16];
+/// Body of the AuthGameGuard packet, padded to the Blowfish block.
+const GAMEGUARD: &str = "0725c7892400000000000000000000000000000000000000";
+
+struct NullWriter;
+
+impl Write for NullWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl WriteClose for NullWriter {
+    fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+type Client = Arc<AuthClient<Box<AuthClientSenderImpl<NullWriter>>>>;
+
+fn session() -> (Client, Arc<Mutex<AuthClientCrypt>>) {
+    let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+    let sender = AuthClientSenderImpl::new(NullWriter, crypt.clone());
+    let client = AuthClient::new(sender).expect("Failed to create client");
+    (client, crypt)
+}
+
+fn client() -> Client {
+    session().0
+}
+
+/// Swap the byte order of the words, as the protocol does around Blowfish.
+fn blowfish_compat(buffer: &mut [u8]) {
+    for word in buffer.chunks_exact_mut(4) {
+        word.reverse();
+    }
+}
+
+/// Client frame body carrying AuthGameGuard, encrypted with the initial key.
+fn gameguard_frame() -> Vec<u8> {
+    let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+    let mut crypt = crypt.lock().expect("Failed to lock crypt");
+    let mut plain = hex::decode(GAMEGUARD).expect("Failed to decode packet");
+    blowfish_compat(&mut plain);
+    let mut encrypted = vec![0; plain.len() + 8];
+    let size = crypt
+        .encrypt
+        .update(&plain, &mut encrypted)
+        .expect("Failed to encrypt");
+    encrypted.truncate(size);
+    encrypted
+}
+
+fn concurrent<T: Sync>(
+    threads: usize,
+    iters: u64,
+    setup: impl Fn() -> T,
+    op: impl Fn(&T) + Sync,
+) -> Duration {
+    let states: Vec<T> = (0..threads).map(|_| setup()).collect();
+    let op = &op;
+    let barrier = &Barrier::new(threads);
+    thread::scope(|scope| {
+        // Time the workers only, spawning and joining stays out of the measurement
+        let workers: Vec<_> = states
+            .iter()
+            .map(|state| {
+                scope.spawn(move || {
+                    barrier.wait();
+                    let start = Instant::now();
+                    for _ in 0..iters {
+                        op(state);
+                    }
+                    start.elapsed()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Worker panicked"))
+            .max()
+            .unwrap_or_default()
+    })
+}
+
+fn init(c: &mut Criterion) {
+    let mut group = c.benchmark_group("init");
+    for &threads in CONCURRENCY {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &n| {
+            b.iter_custom(|iters| {
+                concurrent(n, iters, client, |client| {
+                    client.init().expect("Failed to init")
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Round trip of GameGuard: decrypt and decode the request, then encode and encrypt the reply.
+fn gameguard(c: &mut Criterion) {
+    let frame = gameguard_frame();
+    let frame = &frame;
+    let mut group = c.benchmark_group("gameguard");
+    for &threads in CONCURRENCY {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &n| {
+            b.iter_custom(|iters| {
+                concurrent(n, iters, session, |(client, crypt)| {
+                    let mut decrypted = vec![0; frame.len() + 8];
+                    let size = {
+                        let mut crypt = crypt.lock().expect("Failed to lock crypt");
+                        crypt
+                            .decrypt
+                            .update(frame, &mut decrypted)
+                            .expect("Failed to decrypt")
+                    };
+                    blowfish_compat(&mut decrypted[..size]);
+                    let msg = decode_with(
+                        &mut Cursor::new(&decrypted[..size]),
+                        UnknownOpcodePolicy::Disconnect,
+                    )
+                    .expect("Failed to decode")
+                    .expect("Missing message");
+                    client.handle(msg).expect("Failed to handle")
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Raw RSA decryption of a credentials block, the crate does not decrypt credentials yet.
+fn credentials(c: &mut Criterion) {
+    let key = Rsa::generate(1024).expect("Failed to generate key");
+    // Every session owns its key, cloning would share one OpenSSL object between workers
+    let der = key.private_key_to_der().expect("Failed to export key");
+    let der = &der;
+    let mut plain = [0u8; 128];
+    plain[1..].fill(0x5a);
+    let mut encrypted = [0u8; 128];
+    key.public_encrypt(&plain, &mut encrypted, Padding::NONE)
+        .expect("Failed to encrypt");
+
+    let mut group = c.benchmark_group("credentials");
+    for &threads in CONCURRENCY {
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &n| {
+            b.iter_custom(|iters| {
+                concurrent(
+                    n,
+                    iters,
+                    || Rsa::private_key_from_der(der).expect("Failed to import key"),
+                    |key| {
+                        let mut decrypted = [0u8; 128];
+                        key.private_decrypt(&encrypted, &mut decrypted, Padding::NONE)
+                            .expect("Failed to decrypt");
+                    },
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, init, gameguard, credentials);
+criterion_main!(benches);
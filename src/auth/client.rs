@@ -1,4 +1,4 @@
-use crate::auth::message::ServerMessage;
+use crate::auth::message::{ClientMessage, GGAuthResult, ServerMessage, PROTOCOL_VERSION};
 use crate::auth::sender::AuthClientSender;
 use anyhow::Result;
 use openssl::pkey::Private;
@@ -6,16 +6,21 @@ use openssl::rand::rand_bytes;
 use openssl::rsa::Rsa;
 use std::io::{Error, ErrorKind};
 use std::sync::{Arc, Mutex, MutexGuard};
+use zeroize::Zeroizing;
 
-pub struct AuthClient {
-    state: Mutex<AuthClientState>,
+/// Connected auth client, owning the session keys.
+///
+/// Boxed sender is used by default, pass a concrete one to avoid dynamic dispatch.
+pub struct AuthClient<S = Box<dyn AuthClientSender>> {
+    state: Mutex<AuthClientState<S>>,
 }
 
-impl AuthClient {
-    pub fn new(sender: Box<dyn AuthClientSender>) -> Result<Arc<Self>> {
+impl<S: AuthClientSender> AuthClient<S> {
+    /// Create a client with freshly generated keys.
+    pub fn new(sender: S) -> Result<Arc<Self>> {
         // Generate keys for traffic/credential encryption
-        let mut crypt_key = [0; 16];
-        rand_bytes(&mut crypt_key)?;
+        let mut crypt_key = Zeroizing::new([0; 16]);
+        rand_bytes(&mut *crypt_key)?;
         let credentials_key = Rsa::generate(1024)?;
 
         // Construct client
@@ -28,33 +33,60 @@ impl AuthClient {
         }))
     }
 
+    /// Send the initial packet with the session keys.
     pub fn init(&self) -> Result<()> {
         let mut state = self.state()?;
         let msg = ServerMessage::Init {
             session_id: 0x1eadbeef,
-            modulus: state
-                .credentials_key
-                .n()
-                .to_vec()
-                .try_into()
-                .expect("Invalid modulus length"),
-            crypt_key: state.crypt_key,
+            revision: PROTOCOL_VERSION,
+            modulus: modulus(&state.credentials_key)?,
+            crypt_key: state.crypt_key.clone(),
         };
         state.sender.send(msg)?;
         Ok(())
     }
 
-    fn state(&self) -> std::io::Result<MutexGuard<AuthClientState>> {
+    /// Handle a message received from the client.
+    pub fn handle(&self, msg: ClientMessage) -> Result<()> {
+        match msg {
+            ClientMessage::AuthGameGuard {} => self.on_gameguard(),
+        }
+    }
+
+    fn on_gameguard(&self) -> Result<()> {
+        let mut state = self.state()?;
+        let msg = ServerMessage::GGAuth {
+            result: GGAuthResult::Skip,
+        };
+        state.sender.send(msg)?;
+        Ok(())
+    }
+
+    fn state(&self) -> std::io::Result<MutexGuard<'_, AuthClientState<S>>> {
         self.state
             .lock()
             .map_err(|_| Error::new(ErrorKind::Other, "Cannot unlock state"))
     }
 }
 
-struct AuthClientState {
-    sender: Box<dyn AuthClientSender>,
+fn modulus(key: &Rsa<Private>) -> std::io::Result<[u8; 128]> {
+    // Modulus can be shorter when the leading byte is small, pad it with zeros
+    let n = key.n().to_vec();
+    let mut modulus = [0; 128];
+    if n.len() > modulus.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid modulus length ({})", n.len()),
+        ));
+    }
+    modulus[128 - n.len()..].copy_from_slice(&n);
+    Ok(modulus)
+}
 
-    crypt_key: [u8; 16],
+struct AuthClientState<S> {
+    sender: S,
+
+    crypt_key: Zeroizing<[u8; 16]>,
     credentials_key: Rsa<Private>,
 }
 
@@ -63,7 +95,6 @@ mod tests {
     use super::*;
     use crate::auth::sender::MockAuthClientSender;
     use mockall::predicate;
-    use std::io::{Error, ErrorKind};
 
     #[test]
     fn init_success() {
@@ -71,9 +102,8 @@ mod tests {
         let mut sender = Box::new(MockAuthClientSender::new());
         sender
             .expect_send()
-            .with(predicate::function(|msg: &ServerMessage| match msg {
-                ServerMessage::Init { .. } => true,
-                _ => false,
+            .with(predicate::function(|msg: &ServerMessage| {
+                matches!(msg, ServerMessage::Init { .. })
             }))
             .times(1)
             .returning(|_| Ok(()));
@@ -92,9 +122,8 @@ mod tests {
         let mut sender = Box::new(MockAuthClientSender::new());
         sender
             .expect_send()
-            .with(predicate::function(|msg: &ServerMessage| match msg {
-                ServerMessage::Init { .. } => true,
-                _ => false,
+            .with(predicate::function(|msg: &ServerMessage| {
+                matches!(msg, ServerMessage::Init { .. })
             }))
             .times(1)
             .returning(|_| Err(Error::from(ErrorKind::InvalidData)));
@@ -106,4 +135,74 @@ mod tests {
         // Assert
         assert_eq!(result.is_err(), true);
     }
+
+    #[test]
+    fn handle_gameguard_success() {
+        // Arrange
+        let mut sender = Box::new(MockAuthClientSender::new());
+        sender
+            .expect_send()
+            .with(predicate::function(|msg: &ServerMessage| {
+                matches!(msg, ServerMessage::GGAuth { .. })
+            }))
+            .times(1)
+            .returning(|_| Ok(()));
+        let client = AuthClient::new(sender).expect("Failed to create client");
+
+        // Act
+        let result = client.handle(ClientMessage::AuthGameGuard {});
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn handle_gameguard_fail() {
+        // Arrange
+        let mut sender = Box::new(MockAuthClientSender::new());
+        sender
+            .expect_send()
+            .with(predicate::function(|msg: &ServerMessage| {
+                matches!(msg, ServerMessage::GGAuth { .. })
+            }))
+            .times(1)
+            .returning(|_| Err(Error::from(ErrorKind::InvalidData)));
+        let client = AuthClient::new(sender).expect("Failed to create client");
+
+        // Act
+        let result = client.handle(ClientMessage::AuthGameGuard {});
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+    }
+
+    #[test]
+    fn modulus_short() {
+        // Arrange
+        let key = Rsa::generate(1016).expect("Failed to generate key");
+
+        // Act
+        let result = modulus(&key);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        let modulus = result.unwrap();
+        assert_eq!(modulus[0], 0);
+        assert_eq!(modulus[1..], key.n().to_vec()[..]);
+    }
+
+    #[test]
+    fn modulus_long() {
+        // Arrange
+        let key = Rsa::generate(1032).expect("Failed to generate key");
+
+        // Act
+        let result = modulus(&key);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Invalid modulus length (129)");
+    }
 }
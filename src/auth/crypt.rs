@@ -1,10 +1,14 @@
-use crate::auth::BLOCK_SIZE;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use openssl::symm::{Cipher, Crypter, Mode};
-use std::io::{Cursor, Result};
+use std::io::{Cursor, Error, ErrorKind, Result};
 use std::num::Wrapping;
 use std::sync::{Arc, Mutex};
 
+/// Size of the words the checksum and the scrambling work on.
+pub const WORD_SIZE: usize = 4;
+/// Block size of Blowfish, the body is padded to a multiple of it before encryption.
+pub const CIPHER_BLOCK_SIZE: usize = 8;
+
 pub fn scramble_modulus(modulus: &mut [u8]) {
     for i in 0..4 {
         modulus.swap(i, i + 77);
@@ -20,10 +24,25 @@ pub fn scramble_modulus(modulus: &mut [u8]) {
     }
 }
 
+pub fn unscramble_modulus(modulus: &mut [u8]) {
+    for i in 0..64 {
+        modulus[i + 64] ^= modulus[i];
+    }
+    for i in 0..4 {
+        modulus[i + 13] ^= modulus[i + 52];
+    }
+    for i in 0..64 {
+        modulus[i] ^= modulus[i + 64];
+    }
+    for i in 0..4 {
+        modulus.swap(i, i + 77);
+    }
+}
+
 pub fn scramble_init(buffer: &mut [u8], size: usize, key: i32) -> Result<()> {
     // Scramble
     let mut key = Wrapping(key);
-    for offset in (BLOCK_SIZE..size).step_by(BLOCK_SIZE) {
+    for offset in (WORD_SIZE..size).step_by(WORD_SIZE) {
         let mut block = Cursor::new(&buffer[offset..]).read_i32::<LittleEndian>()?;
         key += block;
         block ^= key.0;
@@ -35,8 +54,22 @@ pub fn scramble_init(buffer: &mut [u8], size: usize, key: i32) -> Result<()> {
     Ok(())
 }
 
+pub fn unscramble_init(buffer: &mut [u8], size: usize) -> Result<()> {
+    // Read the key
+    let mut key = Wrapping(Cursor::new(&buffer[size..]).read_i32::<LittleEndian>()?);
+
+    // Unscramble
+    for offset in (WORD_SIZE..size).step_by(WORD_SIZE).rev() {
+        let mut block = Cursor::new(&buffer[offset..]).read_i32::<LittleEndian>()?;
+        block ^= key.0;
+        key -= block;
+        Cursor::new(&mut buffer[offset..]).write_i32::<LittleEndian>(block)?;
+    }
+    Ok(())
+}
+
 pub fn blowfish_compat(buffer: &mut [u8]) {
-    for offset in (0..buffer.len()).step_by(BLOCK_SIZE) {
+    for offset in (0..buffer.len()).step_by(WORD_SIZE) {
         buffer.swap(offset, offset + 3);
         buffer.swap(offset + 1, offset + 2);
     }
@@ -44,41 +77,60 @@ pub fn blowfish_compat(buffer: &mut [u8]) {
 
 pub fn checksum(buffer: &[u8]) -> Result<i32> {
     let mut result = 0;
-    for offset in (0..buffer.len()).step_by(BLOCK_SIZE) {
+    for offset in (0..buffer.len()).step_by(WORD_SIZE) {
         let block = Cursor::new(&buffer[offset..]).read_i32::<LittleEndian>()?;
         result ^= block;
     }
     Ok(result)
 }
 
+/// Blowfish state for the client traffic.
+///
+/// OpenSSL 3 ships Blowfish in the legacy provider, which has to be enabled
+/// (for example through `OPENSSL_CONF`) for the state to be created.
 pub struct AuthClientCrypt {
+    /// Encrypts outgoing traffic.
     pub encrypt: Crypter,
+    /// Decrypts incoming traffic.
     pub decrypt: Crypter,
 }
 
 impl AuthClientCrypt {
+    /// Create the state for the key.
     pub fn new(key: &[u8]) -> Result<Arc<Mutex<Self>>> {
-        let mut encrypt = Crypter::new(Cipher::bf_ecb(), Mode::Encrypt, key, None)?;
-        encrypt.pad(false);
-        let mut decrypt = Crypter::new(Cipher::bf_ecb(), Mode::Decrypt, key, None)?;
-        decrypt.pad(false);
+        let encrypt = blowfish(Mode::Encrypt, key)?;
+        let decrypt = blowfish(Mode::Decrypt, key)?;
 
         Ok(Arc::new(Mutex::new(Self { encrypt, decrypt })))
     }
 
+    /// Replace the key for both directions.
     pub fn update_key(&mut self, key: &[u8]) -> Result<()> {
-        self.encrypt = Crypter::new(Cipher::bf_ecb(), Mode::Encrypt, key, None)?;
-        self.encrypt.pad(false);
-        self.decrypt = Crypter::new(Cipher::bf_ecb(), Mode::Decrypt, key, None)?;
-        self.decrypt.pad(false);
+        self.encrypt = blowfish(Mode::Encrypt, key)?;
+        self.decrypt = blowfish(Mode::Decrypt, key)?;
         Ok(())
     }
 }
 
+fn blowfish(mode: Mode, key: &[u8]) -> Result<Crypter> {
+    let mut crypter = Crypter::new(Cipher::bf_ecb(), mode, key, None).map_err(|e| {
+        Error::new(
+            ErrorKind::Other,
+            format!(
+                "Cannot create blowfish cipher, is the OpenSSL legacy provider enabled? {}",
+                e
+            ),
+        )
+    })?;
+    crypter.pad(false);
+    Ok(crypter)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::auth::INIT_KEY;
+    use crate::auth::{BUFFER_SIZE, INIT_KEY};
+    use proptest::prelude::*;
 
     #[test]
     fn scramble_modulus_success() {
@@ -106,11 +158,27 @@ mod tests {
         scramble_modulus(&mut modulus);
     }
 
+    #[test]
+    fn unscramble_modulus_success() {
+        // Arrange
+        let mut modulus = hex::decode("768ca46255674d1df5485e9f1556e7b0928f1cbfe481de9e1c15b928c01763a2d762f27d10d8ff58896f0046da4589c47fa926765abae23c7475f5cf745efb295fee3140023723947d0ebdccefccc0c6fb15018df6ce66414fccd0f5bab54124b8caac6d7f52f8bbbab7de926b4f0ac4cc84793196e44928774a57737d0e4ee0").
+            expect("Failed to decode modulus");
+
+        // Act
+        unscramble_modulus(&mut modulus);
+
+        // Assert
+        assert_eq!(
+            hex::encode(modulus),
+            "9a277669023723947d0ebdccef967a24c715018df6ce66414fccd0f5bab54124b8caac6d7f52f8bbbab7de926b4f0ac4cc84793196e44928774a57737d0e4ee02962952257506e898846e353fa5fee31409a1d32124fb8df53d969dd7aa222866fa85e106f8a07e333d8ded4b10a8300b32d5f47cc5eab14033fa2bc0950b5c9",
+        );
+    }
+
     #[test]
     fn scramble_init_success() {
         // Arrange
         let mut buffer = hex::decode("010203040506070800000000").expect("Failed to decode buffer");
-        let size = buffer.len() - BLOCK_SIZE;
+        let size = buffer.len() - WORD_SIZE;
         let key = -559038737;
 
         // Act
@@ -135,6 +203,33 @@ mod tests {
         assert_eq!(result.is_err(), true);
     }
 
+    #[test]
+    fn unscramble_init_success() {
+        // Arrange
+        let mut buffer = hex::decode("01020304f1c2b3eef4c4b4e6").expect("Failed to decode buffer");
+        let size = buffer.len() - WORD_SIZE;
+
+        // Act
+        let result = unscramble_init(&mut buffer, size);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(hex::encode(&buffer[..size]), "0102030405060708");
+    }
+
+    #[test]
+    fn unscramble_init_fail() {
+        // Arrange
+        let mut buffer = hex::decode("0102030405060708").expect("Failed to decode buffer");
+        let size = buffer.len();
+
+        // Act
+        let result = unscramble_init(&mut buffer, size);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+    }
+
     #[test]
     fn blowfish_compat_success() {
         // Arrange
@@ -181,4 +276,48 @@ mod tests {
         assert_eq!(result.is_ok(), true);
         assert_eq!(result.unwrap(), 0xc040404);
     }
+
+    fn blocks(block_size: usize) -> impl Strategy<Value = Vec<u8>> {
+        (1..BUFFER_SIZE / block_size)
+            .prop_flat_map(move |count| prop::collection::vec(any::<u8>(), count * block_size))
+    }
+
+    proptest! {
+        #[test]
+        fn scramble_modulus_inverse(modulus in prop::collection::vec(any::<u8>(), 128)) {
+            let mut buffer = modulus.clone();
+            scramble_modulus(&mut buffer);
+            unscramble_modulus(&mut buffer);
+            prop_assert_eq!(buffer, modulus);
+        }
+
+        #[test]
+        fn scramble_init_inverse(plain in blocks(WORD_SIZE), key in any::<i32>()) {
+            let size = plain.len();
+            let mut buffer = plain.clone();
+            buffer.resize(size + WORD_SIZE, 0);
+            scramble_init(&mut buffer, size, key).expect("Failed to scramble");
+            unscramble_init(&mut buffer, size).expect("Failed to unscramble");
+            prop_assert_eq!(&buffer[..size], &plain[..]);
+        }
+
+        #[test]
+        fn blowfish_compat_involution(plain in blocks(WORD_SIZE)) {
+            let mut buffer = plain.clone();
+            blowfish_compat(&mut buffer);
+            blowfish_compat(&mut buffer);
+            prop_assert_eq!(buffer, plain);
+        }
+
+        #[test]
+        fn crypt_inverse(plain in blocks(CIPHER_BLOCK_SIZE), key in any::<[u8; 16]>()) {
+            let crypt = AuthClientCrypt::new(&key).expect("Failed to new");
+            let mut state = crypt.lock().expect("Failed to lock");
+            let mut encrypted = vec![0; plain.len() + CIPHER_BLOCK_SIZE];
+            let size = state.encrypt.update(&plain, &mut encrypted).expect("Failed to encrypt");
+            let mut decrypted = vec![0; plain.len() + CIPHER_BLOCK_SIZE];
+            let size = state.decrypt.update(&encrypted[..size], &mut decrypted).expect("Failed to decrypt");
+            prop_assert_eq!(&decrypted[..size], &plain[..]);
+        }
+    }
 }
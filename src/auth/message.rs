@@ -1,70 +1,430 @@
 use crate::auth::crypt::scramble_modulus;
+use std::fmt;
 use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::net::Ipv4Addr;
 
 use crate::io::{ReadMMO, WriteMMO};
+use log::warn;
+use zeroize::Zeroizing;
 
+/// Opcode of a message sent by the server.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ServerOpcode {
+    /// Initial packet with the session keys.
+    Init = 0x00,
+    /// Login failure with the reason.
+    LoginFail = 0x01,
+    /// List of the game servers.
+    ServerList = 0x04,
+    /// Response to the GameGuard authentication.
+    GGAuth = 0x0b,
+    /// Login failure on the one-time password check.
+    LoginOptFail = 0x0d,
+}
+
+impl TryFrom<u8> for ServerOpcode {
+    type Error = u8;
+
+    fn try_from(id: u8) -> std::result::Result<Self, Self::Error> {
+        match id {
+            0x00 => Ok(Self::Init),
+            0x01 => Ok(Self::LoginFail),
+            0x04 => Ok(Self::ServerList),
+            0x0b => Ok(Self::GGAuth),
+            0x0d => Ok(Self::LoginOptFail),
+            id => Err(id),
+        }
+    }
+}
+
+/// Opcode of a message sent by the client.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClientOpcode {
+    /// GameGuard authentication request.
+    AuthGameGuard = 0x07,
+}
+
+impl TryFrom<u8> for ClientOpcode {
+    type Error = u8;
+
+    fn try_from(id: u8) -> std::result::Result<Self, Self::Error> {
+        match id {
+            0x07 => Ok(Self::AuthGameGuard),
+            id => Err(id),
+        }
+    }
+}
+
+/// Result of the GameGuard authentication.
 #[derive(Debug)]
 pub enum GGAuthResult {
+    /// GameGuard check is skipped.
     Skip = 0x0b,
 }
 
-#[derive(Debug)]
+/// Reason of the login failure.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LoginFailReason {
+    /// System error, try again later.
+    SystemError = 0x01,
+    /// Password is wrong.
+    PassWrong = 0x02,
+    /// User or password is wrong.
+    UserOrPassWrong = 0x03,
+    /// Access failed, try again later.
+    AccessFailed = 0x04,
+    /// Account is already in use.
+    AccountInUse = 0x07,
+    /// Server is overloaded.
+    ServerOverloaded = 0x0f,
+    /// Server is under maintenance.
+    ServerMaintenance = 0x10,
+    /// Temporary password has expired.
+    TempPassExpired = 0x11,
+    /// Prepaid game time has expired.
+    GameTimeExpired = 0x12,
+    /// No play time left on the account.
+    NoTimeLeft = 0x13,
+    /// Weekly usage time is used up.
+    WeekUsageFinished = 0x1e,
+}
+
+/// Display flags of a game server in the list.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ServerFlags {
+    /// Show the brackets around the name.
+    pub brackets: bool,
+    /// Show the clock next to the name.
+    pub clock: bool,
+    /// Mark as a test server.
+    pub test_server: bool,
+}
+
+impl ServerFlags {
+    fn bits(&self) -> i32 {
+        let mut bits = 0;
+        if self.clock {
+            bits |= 0x02;
+        }
+        if self.test_server {
+            bits |= 0x04;
+        }
+        bits
+    }
+}
+
+/// Game server entry in the list.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ServerInfo {
+    /// Server identifier.
+    pub id: u8,
+    /// Address the client connects to.
+    pub ip: Ipv4Addr,
+    /// Port the client connects to.
+    pub port: i32,
+    /// Minimal age of the players.
+    pub age_limit: u8,
+    /// Whether PvP is enabled.
+    pub pvp: bool,
+    /// Number of players online.
+    pub current_players: i16,
+    /// Maximal number of players.
+    pub max_players: i16,
+    /// Whether the server is up.
+    pub online: bool,
+    /// Display flags.
+    pub flags: ServerFlags,
+}
+
+/// Message sent by the server.
 pub enum ServerMessage {
+    /// Initial packet with the session keys.
     Init {
+        /// Session identifier.
         session_id: i32,
+        /// Protocol revision, selects the GameGuard block.
+        revision: i32,
+        /// RSA modulus for the credentials.
         modulus: [u8; 128],
-        crypt_key: [u8; 16],
+        /// Blowfish key for the traffic, wiped when the message is dropped.
+        crypt_key: Zeroizing<[u8; 16]>,
+    },
+    /// Login failure with the reason.
+    LoginFail {
+        /// Reason of the failure.
+        reason: LoginFailReason,
     },
+    /// List of the game servers.
+    ServerList {
+        /// Server the client played on last time.
+        last_server: u8,
+        /// Servers to show.
+        servers: Vec<ServerInfo>,
+    },
+    /// Response to the GameGuard authentication.
     GGAuth {
+        /// Result of the check.
         result: GGAuthResult,
     },
+    /// Login failure on the one-time password check.
+    LoginOptFail {},
+}
+
+impl ServerMessage {
+    /// Opcode of the message.
+    pub fn opcode(&self) -> ServerOpcode {
+        match self {
+            ServerMessage::Init { .. } => ServerOpcode::Init,
+            ServerMessage::LoginFail { .. } => ServerOpcode::LoginFail,
+            ServerMessage::ServerList { .. } => ServerOpcode::ServerList,
+            ServerMessage::GGAuth { .. } => ServerOpcode::GGAuth,
+            ServerMessage::LoginOptFail {} => ServerOpcode::LoginOptFail,
+        }
+    }
+}
+
+impl fmt::Debug for ServerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerMessage::Init {
+                session_id,
+                revision,
+                modulus,
+                ..
+            } => f
+                .debug_struct("Init")
+                .field("session_id", session_id)
+                .field("revision", revision)
+                .field("modulus", modulus)
+                .field("crypt_key", &format_args!("<redacted>"))
+                .finish(),
+            ServerMessage::LoginFail { reason } => {
+                f.debug_struct("LoginFail").field("reason", reason).finish()
+            }
+            ServerMessage::ServerList {
+                last_server,
+                servers,
+            } => f
+                .debug_struct("ServerList")
+                .field("last_server", last_server)
+                .field("servers", servers)
+                .finish(),
+            ServerMessage::GGAuth { result } => {
+                f.debug_struct("GGAuth").field("result", result).finish()
+            }
+            ServerMessage::LoginOptFail {} => f.debug_struct("LoginOptFail").finish(),
+        }
+    }
 }
 
-const PROTOCOL_VERSION: i32 = 0xc621;
+/// Protocol revision with the GameGuard block in the initial packet.
+pub const PROTOCOL_VERSION: i32 = 0xc621;
+
+/// GameGuard block following the modulus in the initial packet.
+fn gameguard_block(revision: i32) -> [i32; 4] {
+    match revision {
+        PROTOCOL_VERSION => [0x29dd954e, 0x77c39cfc, 0x97adb620_u32 as i32, 0x07bde0f7],
+        _ => [0; 4],
+    }
+}
 
 pub fn encode(msg: ServerMessage, io: &mut (impl Write + Seek)) -> Result<()> {
+    io.write_c(msg.opcode() as i8)?;
     match msg {
         ServerMessage::Init {
             session_id,
+            revision,
             mut modulus,
             crypt_key,
         } => {
             scramble_modulus(&mut modulus);
 
-            io.write_c(0x00)?;
             io.write_d(session_id)?;
-            io.write_d(PROTOCOL_VERSION)?;
+            io.write_d(revision)?;
             io.write_b(&modulus)?;
-            io.seek(SeekFrom::Current(16))?;
-            io.write_b(&crypt_key)?;
+            for block in gameguard_block(revision) {
+                io.write_d(block)?;
+            }
+            io.write_b(&*crypt_key)?;
+        }
+        ServerMessage::LoginFail { reason } => {
+            io.write_d(reason as i32)?;
+        }
+        ServerMessage::ServerList {
+            last_server,
+            servers,
+        } => {
+            if servers.len() > u8::MAX as usize {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Too many servers ({})", servers.len()),
+                ));
+            }
+            io.write_c(servers.len() as i8)?;
+            io.write_c(last_server as i8)?;
+            for server in servers {
+                io.write_c(server.id as i8)?;
+                io.write_b(&server.ip.octets())?;
+                io.write_d(server.port)?;
+                io.write_c(server.age_limit as i8)?;
+                io.write_c(server.pvp as i8)?;
+                io.write_h(server.current_players)?;
+                io.write_h(server.max_players)?;
+                io.write_c(server.online as i8)?;
+                io.write_d(server.flags.bits())?;
+                io.write_c(server.flags.brackets as i8)?;
+            }
         }
         ServerMessage::GGAuth { result } => {
-            io.write_c(0x0b)?;
             io.write_d(result as i32)?;
             io.seek(SeekFrom::Current(16))?;
         }
+        ServerMessage::LoginOptFail {} => {}
     }
     Ok(())
 }
 
+/// Message sent by the client.
 #[derive(PartialEq, Debug)]
 pub enum ClientMessage {
+    /// GameGuard authentication request.
     AuthGameGuard {},
 }
 
+impl ClientMessage {
+    /// Opcode of the message.
+    pub fn opcode(&self) -> ClientOpcode {
+        match self {
+            ClientMessage::AuthGameGuard {} => ClientOpcode::AuthGameGuard,
+        }
+    }
+}
+
+/// Direction of a message.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    /// Sent by the client.
+    Client,
+    /// Sent by the server.
+    Server,
+}
+
+/// Where the decoding failed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DecodeContext {
+    /// Direction of the message.
+    pub direction: Direction,
+    /// Opcode of the message, if it was read.
+    pub opcode: Option<u8>,
+    /// Offset in the message.
+    pub offset: u64,
+}
+
+/// Decoding failure with the context, available as the inner error of [`std::io::Error`].
+///
+/// The underlying failure is part of the message, so it is not reported as the source.
+#[derive(Debug)]
+pub struct DecodeError {
+    /// Where the decoding failed.
+    pub context: DecodeContext,
+    /// Underlying failure.
+    pub source: Error,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = match self.context.direction {
+            Direction::Client => "client",
+            Direction::Server => "server",
+        };
+        match self.context.opcode {
+            Some(opcode) => write!(
+                f,
+                "{} in {} packet 0x{:02x} at offset {}",
+                self.source, direction, opcode, self.context.offset
+            ),
+            None => write!(
+                f,
+                "{} in {} packet at offset {}",
+                self.source, direction, self.context.offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn with_context(direction: Direction, offset: u64, opcode: Option<u8>, err: Error) -> Error {
+    let context = DecodeContext {
+        direction,
+        opcode,
+        offset,
+    };
+    Error::new(
+        err.kind(),
+        DecodeError {
+            context,
+            source: err,
+        },
+    )
+}
+
 pub fn decode(io: &mut (impl Read + Seek)) -> Result<ClientMessage> {
-    match io.read_c()? {
-        0x07 => Ok(ClientMessage::AuthGameGuard {}),
-        id => Err(Error::new(
-            ErrorKind::InvalidData,
-            format!("Invalid packet id (0x{:02x})", id),
+    let offset = io.stream_position()?;
+    let id = io
+        .read_c()
+        .map_err(|err| with_context(Direction::Client, offset, None, err))? as u8;
+    match ClientOpcode::try_from(id) {
+        Ok(ClientOpcode::AuthGameGuard) => Ok(ClientMessage::AuthGameGuard {}),
+        Err(id) => Err(with_context(
+            Direction::Client,
+            offset,
+            Some(id),
+            Error::new(ErrorKind::InvalidData, "Invalid packet id"),
         )),
     }
 }
 
+/// Handling of the messages with unknown opcodes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnknownOpcodePolicy {
+    /// Fail decoding, which closes the connection.
+    Disconnect,
+    /// Skip the message silently.
+    Skip,
+    /// Skip the message with a warning.
+    Log,
+}
+
+/// Decode a client message, handling an unknown opcode according to the policy.
+///
+/// Returns `None` when the message was skipped.
+pub fn decode_with(
+    io: &mut (impl Read + Seek),
+    policy: UnknownOpcodePolicy,
+) -> Result<Option<ClientMessage>> {
+    let offset = io.stream_position()?;
+    let id = io
+        .read_c()
+        .map_err(|err| with_context(Direction::Client, offset, None, err))? as u8;
+    if ClientOpcode::try_from(id).is_err() {
+        match policy {
+            UnknownOpcodePolicy::Disconnect => {}
+            UnknownOpcodePolicy::Skip => return Ok(None),
+            UnknownOpcodePolicy::Log => {
+                warn!("Skipping unknown packet id (0x{:02x})", id);
+                return Ok(None);
+            }
+        }
+    }
+    io.seek(SeekFrom::Current(-1))?;
+    decode(io).map(Some)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::auth::BUFFER_SIZE;
+    use crate::chaos::{Chaos, ChaosConfig};
     use std::io::Cursor;
 
     use super::*;
@@ -76,14 +436,15 @@ mod tests {
         let mut writer = Cursor::new(&mut buffer);
         let msg = ServerMessage::Init{
             session_id: -559038737,
+            revision: PROTOCOL_VERSION,
             modulus: hex::decode("9a277669023723947d0ebdccef967a24c715018df6ce66414fccd0f5bab54124b8caac6d7f52f8bbbab7de926b4f0ac4cc84793196e44928774a57737d0e4ee02962952257506e898846e353fa5fee31409a1d32124fb8df53d969dd7aa222866fa85e106f8a07e333d8ded4b10a8300b32d5f47cc5eab14033fa2bc0950b5c9").
                 expect("Fail to decode modulus").
                 try_into().
                 expect("Invalid modulus length"),
-            crypt_key: hex::decode("0102030405060708090a0b0c0d0e0f10").
+            crypt_key: Zeroizing::new(hex::decode("0102030405060708090a0b0c0d0e0f10").
                 expect("Failed to decode crypt key").
                 try_into().
-                expect("Invalid crypt key length"),
+                expect("Invalid crypt key length")),
         };
 
         // Act
@@ -93,7 +454,138 @@ mod tests {
         let position = writer.position() as usize;
         assert_eq!(result.is_ok(), true);
         assert_eq!(position, 1 + 4 + 4 + 128 + 16 + 16);
-        assert_eq!(hex::encode(&buffer[..position]), "00efbeadde21c60000768ca46255674d1df5485e9f1556e7b0928f1cbfe481de9e1c15b928c01763a2d762f27d10d8ff58896f0046da4589c47fa926765abae23c7475f5cf745efb295fee3140023723947d0ebdccefccc0c6fb15018df6ce66414fccd0f5bab54124b8caac6d7f52f8bbbab7de926b4f0ac4cc84793196e44928774a57737d0e4ee0000000000000000000000000000000000102030405060708090a0b0c0d0e0f10");
+        assert_eq!(hex::encode(&buffer[..position]), "00efbeadde21c60000768ca46255674d1df5485e9f1556e7b0928f1cbfe481de9e1c15b928c01763a2d762f27d10d8ff58896f0046da4589c47fa926765abae23c7475f5cf745efb295fee3140023723947d0ebdccefccc0c6fb15018df6ce66414fccd0f5bab54124b8caac6d7f52f8bbbab7de926b4f0ac4cc84793196e44928774a57737d0e4ee04e95dd29fc9cc37720b6ad97f7e0bd070102030405060708090a0b0c0d0e0f10");
+    }
+
+    #[test]
+    fn server_init_debug() {
+        // Arrange
+        let msg = ServerMessage::Init {
+            session_id: 1,
+            revision: PROTOCOL_VERSION,
+            modulus: [0; 128],
+            crypt_key: Zeroizing::new([0x5a; 16]),
+        };
+
+        // Act
+        let result = format!("{:?}", msg);
+
+        // Assert
+        assert_eq!(result.contains("crypt_key: <redacted>"), true);
+        assert_eq!(result.contains("90"), false);
+    }
+
+    #[test]
+    fn server_init_unknown_revision() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+        let msg = ServerMessage::Init {
+            session_id: 0,
+            revision: 0x785a,
+            modulus: [0; 128],
+            crypt_key: Zeroizing::new([0; 16]),
+        };
+
+        // Act
+        let result = encode(msg, &mut writer);
+
+        // Assert
+        let position = writer.position() as usize;
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(position, 1 + 4 + 4 + 128 + 16 + 16);
+        assert_eq!(hex::encode(&buffer[1 + 4..1 + 4 + 4]), "5a780000");
+        assert_eq!(
+            hex::encode(&buffer[1 + 4 + 4 + 128..1 + 4 + 4 + 128 + 16]),
+            "00000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn server_login_fail() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+        let msg = ServerMessage::LoginFail {
+            reason: LoginFailReason::NoTimeLeft,
+        };
+
+        // Act
+        let result = encode(msg, &mut writer);
+
+        // Assert
+        let position = writer.position() as usize;
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(position, 1 + 4);
+        assert_eq!(hex::encode(&buffer[..position]), "0113000000");
+    }
+
+    #[test]
+    fn server_server_list() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+        let msg = ServerMessage::ServerList {
+            last_server: 1,
+            servers: vec![ServerInfo {
+                id: 1,
+                ip: Ipv4Addr::new(127, 0, 0, 1),
+                port: 7777,
+                age_limit: 0,
+                pvp: true,
+                current_players: 10,
+                max_players: 1000,
+                online: true,
+                flags: ServerFlags {
+                    brackets: true,
+                    clock: true,
+                    test_server: true,
+                },
+            }],
+        };
+
+        // Act
+        let result = encode(msg, &mut writer);
+
+        // Assert
+        let position = writer.position() as usize;
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(position, 1 + 1 + 1 + 21);
+        assert_eq!(
+            hex::encode(&buffer[..position]),
+            "040101017f000001611e000000010a00e803010600000001"
+        );
+    }
+
+    #[test]
+    fn server_server_list_too_many() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+        let server = ServerInfo {
+            id: 1,
+            ip: Ipv4Addr::new(127, 0, 0, 1),
+            port: 7777,
+            age_limit: 0,
+            pvp: false,
+            current_players: 0,
+            max_players: 1000,
+            online: true,
+            flags: ServerFlags::default(),
+        };
+        let msg = ServerMessage::ServerList {
+            last_server: 1,
+            servers: vec![server; 256],
+        };
+
+        // Act
+        let result = encode(msg, &mut writer);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Too many servers (256)");
     }
 
     #[test]
@@ -118,6 +610,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn server_login_opt_fail() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+        let msg = ServerMessage::LoginOptFail {};
+
+        // Act
+        let result = encode(msg, &mut writer);
+
+        // Assert
+        let position = writer.position() as usize;
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(position, 1);
+        assert_eq!(hex::encode(&buffer[..position]), "0d");
+    }
+
     #[test]
     fn client_auth_game_guard() {
         // Arrange
@@ -134,6 +643,33 @@ mod tests {
         assert_eq!(result.unwrap(), message);
     }
 
+    #[test]
+    fn server_opcode() {
+        // Act
+        let result = ServerOpcode::try_from(0x0b);
+
+        // Assert
+        assert_eq!(result, Ok(ServerOpcode::GGAuth));
+    }
+
+    #[test]
+    fn client_opcode() {
+        // Act
+        let result = ClientOpcode::try_from(0x07);
+
+        // Assert
+        assert_eq!(result, Ok(ClientOpcode::AuthGameGuard));
+    }
+
+    #[test]
+    fn client_opcode_invalid() {
+        // Act
+        let result = ClientOpcode::try_from(0xff);
+
+        // Assert
+        assert_eq!(result, Err(0xff));
+    }
+
     #[test]
     fn client_invalid() {
         // Arrange
@@ -147,6 +683,171 @@ mod tests {
         assert_eq!(result.is_err(), true);
         let err = result.unwrap_err();
         assert_eq!(err.kind(), ErrorKind::InvalidData);
-        assert_eq!(err.to_string(), "Invalid packet id (0xff)");
+        assert_eq!(
+            err.to_string(),
+            "Invalid packet id in client packet 0xff at offset 0"
+        );
+    }
+
+    #[test]
+    fn client_disconnected() {
+        // Arrange
+        let buffer = hex::decode("0725c78924").expect("Failed to decode buffer");
+        let config = ChaosConfig {
+            disconnect_after: Some(0),
+            ..Default::default()
+        };
+        let mut reader = Chaos::new(Cursor::new(&buffer), config);
+
+        // Act
+        let result = decode(&mut reader);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn client_corrupt() {
+        // Arrange
+        let buffer = hex::decode("0725c78924").expect("Failed to decode buffer");
+        let config = ChaosConfig {
+            corrupt_header: 1,
+            ..Default::default()
+        };
+        let mut reader = Chaos::new(Cursor::new(&buffer), config);
+
+        // Act
+        let result = decode(&mut reader);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn client_unknown_disconnect() {
+        // Arrange
+        let buffer = hex::decode("ff").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = decode_with(&mut reader, UnknownOpcodePolicy::Disconnect);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "Invalid packet id in client packet 0xff at offset 0"
+        );
+    }
+
+    #[test]
+    fn client_unknown_skip() {
+        // Arrange
+        let buffer = hex::decode("ff").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = decode_with(&mut reader, UnknownOpcodePolicy::Skip);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[test]
+    fn client_unknown_log() {
+        // Arrange
+        let buffer = hex::decode("ff").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = decode_with(&mut reader, UnknownOpcodePolicy::Log);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), None);
+    }
+
+    #[test]
+    fn client_known_with_policy() {
+        // Arrange
+        let buffer = hex::decode("0725c7892400000000000000000000000000000000000000")
+            .expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = decode_with(&mut reader, UnknownOpcodePolicy::Skip);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), Some(ClientMessage::AuthGameGuard {}));
+    }
+
+    #[test]
+    fn client_invalid_context() {
+        // Arrange
+        let buffer = hex::decode("0000ff").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+        reader.set_position(2);
+
+        // Act
+        let result = decode(&mut reader);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        let err = err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<DecodeError>())
+            .expect("Missing decode context");
+        assert_eq!(
+            err.context,
+            DecodeContext {
+                direction: Direction::Client,
+                opcode: Some(0xff),
+                offset: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn client_empty_context() {
+        // Arrange
+        let buffer = Vec::new();
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = decode(&mut reader);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "failed to fill whole buffer in client packet at offset 0"
+        );
+    }
+
+    #[test]
+    fn client_invalid_chain() {
+        // Arrange
+        let buffer = hex::decode("ff").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = decode(&mut reader);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = anyhow::Error::from(result.unwrap_err());
+        assert_eq!(
+            format!("{:#}", err),
+            "Invalid packet id in client packet 0xff at offset 0"
+        );
     }
 }
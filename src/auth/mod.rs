@@ -1,15 +1,34 @@
 //! Auth server implementation.
+use crate::frame::FrameCodec;
+
 mod client;
 mod crypt;
+pub mod diagnostics;
 mod message;
+pub mod secure;
 mod sender;
 
-/// Size of the packet header.
-pub const HEADER_SIZE: usize = 2;
-/// Size of the buffers for IO, packet bodies cannot exceed this.
+pub use client::AuthClient;
+pub use crypt::AuthClientCrypt;
+pub use message::{
+    decode_with, ClientMessage, ClientOpcode, DecodeContext, DecodeError, Direction, GGAuthResult,
+    LoginFailReason, ServerFlags, ServerInfo, ServerMessage, ServerOpcode, UnknownOpcodePolicy,
+    PROTOCOL_VERSION,
+};
+#[cfg(feature = "test-util")]
+pub use sender::MockAuthClientSender;
+pub use sender::{AuthClientSender, AuthClientSenderImpl, WriteClose};
+
+/// Initial size of the buffers for IO, they grow on demand.
 pub const BUFFER_SIZE: usize = 1024;
-/// Size of the block for IO operations.
-pub const BLOCK_SIZE: usize = 4;
+/// Framing of the auth protocol.
+pub const FRAME: FrameCodec = FrameCodec {
+    header_size: 2,
+    block_size: 4,
+    checksum: true,
+    cipher_block_size: 8,
+    max_packet_size: u16::MAX as usize,
+};
 /// Initial encryption key for the traffic.
 pub const INIT_KEY: &[u8] = &[
     0x6B, 0x60, 0xCB, 0x5B, 0x82, 0xCE, 0x90, 0xB1, 0xCC, 0x2B, 0x6C, 0x55, 0x6C, 0x6C, 0x6C, 0x6C,
@@ -0,0 +1,39 @@
+//! Helpers for handling secrets.
+use openssl::memcmp;
+
+/// Compare secrets in constant time, only the length may leak.
+pub fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && memcmp::eq(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eq_same() {
+        // Act
+        let result = eq(&[1, 2, 3, 4], &[1, 2, 3, 4]);
+
+        // Assert
+        assert_eq!(result, true);
+    }
+
+    #[test]
+    fn eq_different() {
+        // Act
+        let result = eq(&[1, 2, 3, 4], &[1, 2, 3, 5]);
+
+        // Assert
+        assert_eq!(result, false);
+    }
+
+    #[test]
+    fn eq_different_length() {
+        // Act
+        let result = eq(&[1, 2, 3, 4], &[1, 2, 3]);
+
+        // Assert
+        assert_eq!(result, false);
+    }
+}
@@ -1,96 +1,175 @@
-use crate::auth::crypt::{blowfish_compat, scramble_init, AuthClientCrypt};
+use crate::auth::crypt::{
+    blowfish_compat, scramble_init, AuthClientCrypt, CIPHER_BLOCK_SIZE, WORD_SIZE,
+};
 use crate::auth::message::{encode, ServerMessage};
-use crate::auth::{BLOCK_SIZE, BUFFER_SIZE, HEADER_SIZE};
-use crate::io::{ReadMMO, WriteMMO};
+use crate::auth::{BUFFER_SIZE, FRAME};
+use crate::frame::FrameCodec;
+use crate::io::{resize_zeroed, wipe, wipe_used, BufCursor, ReadMMO, WriteMMO};
+use bytes::{BufMut, BytesMut};
 use log::debug;
+#[cfg(any(test, feature = "test-util"))]
 use mockall::automock;
 use openssl::rand::rand_bytes;
-use openssl::symm::Cipher;
 use std::io::{Cursor, Error, ErrorKind, Result, Write};
 use std::sync::{Arc, Mutex};
 
-pub struct AuthClientSenderImpl {
-    writer: Box<dyn WriteClose + Send>,
-    packet: Vec<u8>,
-    buffer: Vec<u8>,
+/// Sender encrypting and writing messages to the client.
+///
+/// Boxed writer is used by default, pass a concrete one to avoid dynamic dispatch.
+pub struct AuthClientSenderImpl<W = Box<dyn WriteClose + Send>> {
+    writer: W,
+    packet: BytesMut,
+    buffer: BytesMut,
+    frame: FrameCodec,
     crypt: Arc<Mutex<AuthClientCrypt>>,
 }
 
-#[automock]
+/// Sends messages to the client.
+#[cfg_attr(any(test, feature = "test-util"), automock)]
 pub trait AuthClientSender: Send {
+    /// Send a message.
     fn send(&mut self, msg: ServerMessage) -> Result<()>;
+    /// Close the connection.
     fn close(&self) -> Result<()>;
 }
 
-impl AuthClientSenderImpl {
-    pub fn new(
-        writer: Box<dyn WriteClose + Send>,
+impl<T: AuthClientSender + ?Sized> AuthClientSender for Box<T> {
+    fn send(&mut self, msg: ServerMessage) -> Result<()> {
+        (**self).send(msg)
+    }
+
+    fn close(&self) -> Result<()> {
+        (**self).close()
+    }
+}
+
+impl<W: WriteClose + Send> AuthClientSenderImpl<W> {
+    /// Create a sender over the writer.
+    pub fn new(writer: W, crypt: Arc<Mutex<AuthClientCrypt>>) -> Box<Self> {
+        Self::build(writer, crypt, FRAME)
+    }
+
+    /// Create a sender over the writer with custom framing.
+    ///
+    /// The header holds up to 8 bytes and the packet size limit has to fit in it. The body
+    /// alignment has to be whole words for the checksum, and the cipher block a multiple
+    /// of both the alignment and the Blowfish block.
+    pub fn with_frame(
+        writer: W,
         crypt: Arc<Mutex<AuthClientCrypt>>,
-    ) -> Box<Self> {
+        frame: FrameCodec,
+    ) -> Result<Box<Self>> {
+        if frame.header_size == 0 || frame.header_size > 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported header size ({})", frame.header_size),
+            ));
+        }
+        if frame.block_size == 0 || frame.block_size % WORD_SIZE != 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported block size ({})", frame.block_size),
+            ));
+        }
+        if frame.cipher_block_size == 0
+            || frame.cipher_block_size % frame.block_size != 0
+            || frame.cipher_block_size % CIPHER_BLOCK_SIZE != 0
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Unsupported cipher block size ({})",
+                    frame.cipher_block_size
+                ),
+            ));
+        }
+        if frame.max_packet_size as u64 > u64::MAX >> (64 - frame.header_size * 8) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unsupported packet size limit ({})", frame.max_packet_size),
+            ));
+        }
+        Ok(Self::build(writer, crypt, frame))
+    }
+
+    fn build(writer: W, crypt: Arc<Mutex<AuthClientCrypt>>, frame: FrameCodec) -> Box<Self> {
         Box::new(Self {
             writer,
-            packet: vec![0; BUFFER_SIZE],
-            buffer: vec![0; BUFFER_SIZE],
+            packet: BytesMut::with_capacity(BUFFER_SIZE),
+            buffer: BytesMut::with_capacity(frame.header_size + BUFFER_SIZE),
+            frame,
             crypt,
         })
     }
 
     #[inline]
-    fn pad(&self, size: usize, block_size: usize) -> Result<usize> {
-        let size = if size % block_size != 0 {
-            size + (block_size - size % block_size)
-        } else {
-            size
-        };
-        if size < BUFFER_SIZE {
-            Ok(size)
-        } else {
-            Err(Error::new(
+    fn pad(&mut self, size: usize, block_size: usize) -> Result<usize> {
+        let size = FrameCodec::align(size, block_size);
+        if size > self.frame.max_body_size() {
+            return Err(Error::new(
                 ErrorKind::InvalidData,
-                format!("Buffer size ({}) exceeded limit ({})", size, BUFFER_SIZE),
-            ))
+                format!(
+                    "Buffer size ({}) exceeded limit ({})",
+                    size,
+                    self.frame.max_body_size()
+                ),
+            ));
         }
+
+        // Keep room for the block that follows
+        if self.packet.len() < size + self.frame.block_size {
+            resize_zeroed(&mut self.packet, size + self.frame.block_size);
+        }
+        Ok(size)
     }
 }
 
-impl AuthClientSender for AuthClientSenderImpl {
+impl<W: WriteClose + Send> AuthClientSender for AuthClientSenderImpl<W> {
     fn send(&mut self, msg: ServerMessage) -> Result<()> {
-        debug!("Sending {:?}", msg);
-        let new_crypt_key = if let ServerMessage::Init { crypt_key, .. } = msg {
-            Some(crypt_key)
+        debug!("Sending {:?}: {:?}", msg.opcode(), msg);
+        let new_crypt_key = if let ServerMessage::Init { crypt_key, .. } = &msg {
+            Some(crypt_key.clone())
         } else {
             None
         };
 
-        // Reset buffers for writing
-        self.packet.fill(0);
-        self.buffer.fill(0);
+        // Reset buffers for writing, wiping the previous packet, the buffers
+        // only grow within a send so their length covers everything it wrote
+        wipe_used(&mut self.packet);
+        wipe_used(&mut self.buffer);
 
         // Encode the message
-        let mut writer = Cursor::new(&mut self.packet);
+        let mut writer = BufCursor::new(&mut self.packet, self.frame.max_body_size());
         encode(msg, &mut writer)?;
         let mut size = writer.position() as usize;
 
         // Checksum
-        size = self.pad(size, BLOCK_SIZE)?;
-        let checksum = 0;
-        Cursor::new(&mut self.packet[size..]).write_d(checksum)?;
-        size += BLOCK_SIZE;
+        if self.frame.checksum {
+            size = self.pad(size, self.frame.block_size)?;
+            let checksum = 0;
+            Cursor::new(&mut self.packet[size..]).write_d(checksum)?;
+            size += self.frame.block_size;
+        }
 
         // Additional encryption for the first packet
         if new_crypt_key.is_some() {
             let mut key = [0u8; 4];
             rand_bytes(&mut key)?;
 
-            size = self.pad(size, BLOCK_SIZE)?;
+            size = self.pad(size, self.frame.block_size)?;
             scramble_init(&mut self.packet, size, Cursor::new(key).read_d()?)?;
-            size += BLOCK_SIZE;
+            size += self.frame.block_size;
         }
 
         // Encryption
-        size = self.pad(size, BLOCK_SIZE)?;
+        let header_size = self.frame.header_size;
+        size = self.pad(size, self.frame.block_size)?;
         blowfish_compat(&mut self.packet[..size]);
-        size = self.pad(size, Cipher::bf_ecb().block_size())?;
+        size = self.pad(size, self.frame.cipher_block_size)?;
+        resize_zeroed(
+            &mut self.buffer,
+            header_size + size + self.frame.cipher_block_size,
+        );
         {
             let mut crypt = self
                 .crypt
@@ -98,21 +177,20 @@ impl AuthClientSender for AuthClientSenderImpl {
                 .map_err(|_| Error::new(ErrorKind::Other, "Cannot unlock crypt"))?;
             size = crypt
                 .encrypt
-                .update(&self.packet[..size], &mut self.buffer)?;
+                .update(&self.packet[..size], &mut self.buffer[header_size..])?;
 
             // Change key
-            if new_crypt_key.is_some() {
-                crypt.update_key(&new_crypt_key.unwrap())?;
+            if let Some(new_crypt_key) = new_crypt_key {
+                crypt.update_key(&*new_crypt_key)?;
             }
         }
-        blowfish_compat(&mut self.buffer[..size]);
+        blowfish_compat(&mut self.buffer[header_size..header_size + size]);
+        size += header_size;
 
-        // Header
-        let mut header: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
-        Cursor::new(&mut header[..]).write_h((size + HEADER_SIZE) as i16)?;
+        // Header, prepended so the packet goes out in a single write
+        (&mut self.buffer[..header_size]).put_uint_le(size as u64, header_size);
 
         // Send
-        self.writer.write_all(&header)?;
         self.writer.write_all(&self.buffer[..size])?;
         self.writer.flush()?;
         Ok(())
@@ -123,16 +201,37 @@ impl AuthClientSender for AuthClientSenderImpl {
     }
 }
 
+impl<W> Drop for AuthClientSenderImpl<W> {
+    fn drop(&mut self) {
+        // Plaintext of the last packet may contain keys
+        wipe(&mut self.packet);
+        wipe(&mut self.buffer);
+    }
+}
+
+/// Writer that can be closed.
 pub trait WriteClose: Write {
+    /// Close the writer.
     fn close(&self) -> Result<()>;
 }
 
+impl<T: WriteClose + ?Sized> WriteClose for Box<T> {
+    fn close(&self) -> Result<()> {
+        (**self).close()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::auth::message::{GGAuthResult, ServerFlags, ServerInfo, PROTOCOL_VERSION};
     use crate::auth::INIT_KEY;
+    use crate::chaos::{Chaos, ChaosConfig};
     use mockall::{mock, predicate};
     use std::io::Write;
+    use std::net::Ipv4Addr;
+    use std::time::Duration;
+    use zeroize::Zeroizing;
 
     mock! {
         Writer {}
@@ -151,14 +250,11 @@ mod tests {
         let mut writer = Box::new(MockWriter::new());
         writer
             .expect_write()
-            .with(predicate::function(|buf: &[u8]| buf.len() == 2))
+            .with(predicate::function(|buf: &[u8]| {
+                buf.len() == 186 && buf[..2] == [0xba, 0x00]
+            }))
             .times(1)
-            .returning(|_| Ok(2));
-        writer
-            .expect_write()
-            .with(predicate::function(|buf: &[u8]| buf.len() == 184))
-            .times(1)
-            .returning(|_| Ok(184));
+            .returning(|_| Ok(186));
         writer.expect_flush().times(1).returning(|| Ok(()));
 
         let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
@@ -167,11 +263,275 @@ mod tests {
         // Act
         let result = sender.send(ServerMessage::Init {
             session_id: 0,
+            revision: PROTOCOL_VERSION,
             modulus: [0; 128],
-            crypt_key: [0; 16],
+            crypt_key: Zeroizing::new([0; 16]),
         });
 
         // Assert
         assert_eq!(result.is_ok(), true);
     }
+
+    #[test]
+    fn send_partial_writes() {
+        // Arrange
+        let config = ChaosConfig {
+            max_chunk: Some(3),
+            delay: Some(Duration::from_millis(1)),
+            ..Default::default()
+        };
+        let writer = Box::new(Chaos::new(Vec::new(), config));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let mut sender = AuthClientSenderImpl::new(writer, crypt);
+
+        // Act
+        let result = sender.send(ServerMessage::GGAuth {
+            result: GGAuthResult::Skip,
+        });
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn send_disconnect() {
+        // Arrange
+        let config = ChaosConfig {
+            disconnect_after: Some(5),
+            ..Default::default()
+        };
+        let writer = Box::new(Chaos::new(Vec::new(), config));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let mut sender = AuthClientSenderImpl::new(writer, crypt);
+
+        // Act
+        let result = sender.send(ServerMessage::GGAuth {
+            result: GGAuthResult::Skip,
+        });
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::BrokenPipe);
+    }
+
+    fn server_list() -> ServerMessage {
+        let server = ServerInfo {
+            id: 1,
+            ip: Ipv4Addr::new(127, 0, 0, 1),
+            port: 7777,
+            age_limit: 0,
+            pvp: false,
+            current_players: 0,
+            max_players: 1000,
+            online: true,
+            flags: ServerFlags::default(),
+        };
+        ServerMessage::ServerList {
+            last_server: 1,
+            servers: vec![server; 100],
+        }
+    }
+
+    #[test]
+    fn send_large() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let mut sender = AuthClientSenderImpl::new(writer, crypt);
+
+        // Act
+        let result = sender.send(server_list());
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn with_frame_header_size() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            header_size: 9,
+            ..FRAME
+        };
+
+        // Act
+        let result = AuthClientSenderImpl::with_frame(writer, crypt, frame);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Unsupported header size (9)");
+    }
+
+    #[test]
+    fn send_wide_header() {
+        // Arrange
+        let mut writer = Box::new(MockWriter::new());
+        writer
+            .expect_write()
+            .with(predicate::function(|buf: &[u8]| {
+                buf.len() == 36 && buf[..4] == [0x24, 0x00, 0x00, 0x00]
+            }))
+            .times(1)
+            .returning(|_| Ok(36));
+        writer.expect_flush().times(1).returning(|| Ok(()));
+
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            header_size: 4,
+            max_packet_size: u32::MAX as usize,
+            ..FRAME
+        };
+        let mut sender = AuthClientSenderImpl::with_frame(writer, crypt, frame)
+            .expect("Failed to create sender");
+
+        // Act
+        let result = sender.send(ServerMessage::GGAuth {
+            result: GGAuthResult::Skip,
+        });
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn send_wide_alignment() {
+        // Arrange
+        let mut writer = Box::new(MockWriter::new());
+        writer
+            .expect_write()
+            .with(predicate::function(|buf: &[u8]| {
+                buf.len() == 50 && buf[..2] == [0x32, 0x00]
+            }))
+            .times(1)
+            .returning(|_| Ok(50));
+        writer.expect_flush().times(1).returning(|| Ok(()));
+
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            block_size: 16,
+            cipher_block_size: 16,
+            ..FRAME
+        };
+        let mut sender = AuthClientSenderImpl::with_frame(writer, crypt, frame)
+            .expect("Failed to create sender");
+
+        // Act
+        let result = sender.send(ServerMessage::GGAuth {
+            result: GGAuthResult::Skip,
+        });
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+    }
+
+    #[test]
+    fn with_frame_block_size() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            block_size: 0,
+            ..FRAME
+        };
+
+        // Act
+        let result = AuthClientSenderImpl::with_frame(writer, crypt, frame);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Unsupported block size (0)");
+    }
+
+    #[test]
+    fn with_frame_cipher_block_size() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            block_size: 8,
+            cipher_block_size: 12,
+            ..FRAME
+        };
+
+        // Act
+        let result = AuthClientSenderImpl::with_frame(writer, crypt, frame);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Unsupported cipher block size (12)");
+    }
+
+    #[test]
+    fn with_frame_packet_size() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            max_packet_size: u16::MAX as usize + 1,
+            ..FRAME
+        };
+
+        // Act
+        let result = AuthClientSenderImpl::with_frame(writer, crypt, frame);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), "Unsupported packet size limit (65536)");
+    }
+
+    #[test]
+    fn send_limit() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            max_packet_size: 1024,
+            ..FRAME
+        };
+        let mut sender = AuthClientSenderImpl::with_frame(writer, crypt, frame)
+            .expect("Failed to create sender");
+
+        // Act
+        let result = sender.send(server_list());
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Buffer size (1024) exceeded limit (1022)");
+    }
+
+    #[test]
+    fn send_limit_padding() {
+        // Arrange
+        let writer = Box::new(Chaos::new(Vec::new(), ChaosConfig::default()));
+        let crypt = AuthClientCrypt::new(INIT_KEY).expect("Failed to create crypt");
+        let frame = FrameCodec {
+            max_packet_size: 32,
+            ..FRAME
+        };
+        let mut sender = AuthClientSenderImpl::with_frame(writer, crypt, frame)
+            .expect("Failed to create sender");
+
+        // Act
+        let result = sender.send(ServerMessage::GGAuth {
+            result: GGAuthResult::Skip,
+        });
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Buffer size (32) exceeded limit (30)");
+    }
 }
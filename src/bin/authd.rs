@@ -1,7 +1,18 @@
+use anyhow::Result;
 use log::info;
+use mmo_rs::auth::diagnostics;
+use std::env;
 
-fn main() {
+fn main() -> Result<()> {
     env_logger::init();
 
+    info!("Running self-test");
+    diagnostics::self_test()?;
+    if env::args().skip(1).any(|arg| arg == "--self-test") {
+        info!("Self-test passed");
+        return Ok(());
+    }
+
     info!("Starting auth server");
+    Ok(())
 }
@@ -0,0 +1,194 @@
+//! Fault injection around readers and writers for connection tests.
+use crate::auth::WriteClose;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::Duration;
+
+/// Faults to inject into the stream.
+#[derive(Clone, Default)]
+pub struct ChaosConfig {
+    /// Transfer at most this many bytes per call.
+    pub max_chunk: Option<usize>,
+    /// Sleep before every call.
+    pub delay: Option<Duration>,
+    /// Disconnect once this many bytes were transferred.
+    pub disconnect_after: Option<usize>,
+    /// Flip the bits of this many leading bytes.
+    pub corrupt_header: usize,
+}
+
+/// Reader/writer wrapper injecting faults.
+pub struct Chaos<T> {
+    inner: T,
+    config: ChaosConfig,
+    transferred: usize,
+}
+
+impl<T> Chaos<T> {
+    /// Wrap the stream, injecting the configured faults.
+    pub fn new(inner: T, config: ChaosConfig) -> Self {
+        Self {
+            inner,
+            config,
+            transferred: 0,
+        }
+    }
+
+    /// Unwrap the stream.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn limit(&self, len: usize) -> usize {
+        let len = self.config.max_chunk.map_or(len, |chunk| len.min(chunk));
+        self.config
+            .disconnect_after
+            .map_or(len, |after| len.min(after.saturating_sub(self.transferred)))
+    }
+
+    fn disconnected(&self) -> bool {
+        self.config
+            .disconnect_after
+            .is_some_and(|after| self.transferred >= after)
+    }
+
+    fn delay(&self) {
+        if let Some(delay) = self.config.delay {
+            thread::sleep(delay);
+        }
+    }
+
+    fn corrupt(&self, buf: &mut [u8]) {
+        for (offset, byte) in buf.iter_mut().enumerate() {
+            if self.transferred + offset < self.config.corrupt_header {
+                *byte ^= 0xff;
+            }
+        }
+    }
+}
+
+impl<T: Read> Read for Chaos<T> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.delay();
+        if self.disconnected() {
+            return Ok(0);
+        }
+        let len = self.limit(buf.len());
+        let size = self.inner.read(&mut buf[..len])?;
+        self.corrupt(&mut buf[..size]);
+        self.transferred += size;
+        Ok(size)
+    }
+}
+
+impl<T: Write> Write for Chaos<T> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.delay();
+        if self.disconnected() {
+            return Err(Error::from(ErrorKind::BrokenPipe));
+        }
+        let len = self.limit(buf.len());
+        let mut chunk = buf[..len].to_vec();
+        self.corrupt(&mut chunk);
+        let size = self.inner.write(&chunk)?;
+        self.transferred += size;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if self.disconnected() {
+            return Err(Error::from(ErrorKind::BrokenPipe));
+        }
+        self.inner.flush()
+    }
+}
+
+impl<T: Seek> Seek for Chaos<T> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<T: Write> WriteClose for Chaos<T> {
+    fn close(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_partial() {
+        // Arrange
+        let config = ChaosConfig {
+            max_chunk: Some(2),
+            ..Default::default()
+        };
+        let mut reader = Chaos::new(Cursor::new(vec![1, 2, 3, 4]), config);
+        let mut buffer = [0; 4];
+
+        // Act
+        let result = reader.read(&mut buffer);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), 2);
+    }
+
+    #[test]
+    fn read_disconnect() {
+        // Arrange
+        let config = ChaosConfig {
+            disconnect_after: Some(3),
+            ..Default::default()
+        };
+        let mut reader = Chaos::new(Cursor::new(vec![1, 2, 3, 4]), config);
+        let mut buffer = Vec::new();
+
+        // Act
+        let result = reader.read_to_end(&mut buffer);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(buffer, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn read_corrupt_header() {
+        // Arrange
+        let config = ChaosConfig {
+            corrupt_header: 2,
+            ..Default::default()
+        };
+        let mut reader = Chaos::new(Cursor::new(vec![1, 2, 3, 4]), config);
+        let mut buffer = Vec::new();
+
+        // Act
+        let result = reader.read_to_end(&mut buffer);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(buffer, vec![0xfe, 0xfd, 3, 4]);
+    }
+
+    #[test]
+    fn write_disconnect() {
+        // Arrange
+        let config = ChaosConfig {
+            disconnect_after: Some(3),
+            ..Default::default()
+        };
+        let mut writer = Chaos::new(Vec::new(), config);
+
+        // Act
+        let result = writer.write_all(&[1, 2, 3, 4]);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::BrokenPipe);
+        assert_eq!(writer.into_inner(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,57 @@
+//! Framing of the packets on the wire.
+
+/// Describes how the packets of a protocol are framed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrameCodec {
+    /// Size of the packet header, holding the little-endian packet size.
+    pub header_size: usize,
+    /// Alignment of the packet body.
+    pub block_size: usize,
+    /// Whether the body ends with a checksum block.
+    pub checksum: bool,
+    /// Block size of the cipher, the body is padded to it before encryption.
+    pub cipher_block_size: usize,
+    /// Maximal size of a packet including the header.
+    pub max_packet_size: usize,
+}
+
+impl FrameCodec {
+    /// Round the size up to the block size.
+    #[inline]
+    pub fn align(size: usize, block_size: usize) -> usize {
+        if size % block_size != 0 {
+            size + (block_size - size % block_size)
+        } else {
+            size
+        }
+    }
+
+    /// Maximal size of the packet body.
+    #[inline]
+    pub fn max_body_size(&self) -> usize {
+        self.max_packet_size.saturating_sub(self.header_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align_success() {
+        // Act
+        let result = FrameCodec::align(5, 4);
+
+        // Assert
+        assert_eq!(result, 8);
+    }
+
+    #[test]
+    fn align_aligned() {
+        // Act
+        let result = FrameCodec::align(8, 4);
+
+        // Assert
+        assert_eq!(result, 8);
+    }
+}
@@ -1,8 +1,10 @@
 //! Low-level primitives for IO operations.
 
-use std::io::{Read, Result, Write};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, BufMut, BytesMut};
+use zeroize::Zeroize;
 
 /// Extends the writer to support writing MMO values.
 pub trait WriteMMO: Write {
@@ -29,6 +31,20 @@ pub trait WriteMMO: Write {
     fn write_d(&mut self, n: i32) -> Result<()> {
         self.write_i32::<LittleEndian>(n)
     }
+
+    /// Write S value (null-terminated UTF-16LE), embedded nulls are rejected.
+    fn write_s(&mut self, s: &str) -> Result<()> {
+        if s.contains('\0') {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "String contains a null character",
+            ));
+        }
+        for unit in s.encode_utf16() {
+            self.write_u16::<LittleEndian>(unit)?;
+        }
+        self.write_u16::<LittleEndian>(0)
+    }
 }
 
 impl<T: Write> WriteMMO for T {}
@@ -52,14 +68,234 @@ pub trait ReadMMO: Read {
     fn read_d(&mut self) -> Result<i32> {
         self.read_i32::<LittleEndian>()
     }
+
+    /// Read S value (null-terminated UTF-16LE), anything after the terminator is left unread.
+    fn read_s(&mut self) -> Result<String> {
+        read_utf16(self, usize::MAX)
+    }
+
+    /// Read S value limited to the number of UTF-16 units, sanitized for handlers.
+    fn read_s_limited(&mut self, max_len: usize) -> Result<String> {
+        Ok(sanitize(&read_utf16(self, max_len)?))
+    }
 }
 
 impl<T: Read> ReadMMO for T {}
 
+fn read_utf16<R: Read + ?Sized>(reader: &mut R, max_len: usize) -> Result<String> {
+    let mut units = Vec::new();
+    loop {
+        match reader.read_u16::<LittleEndian>()? {
+            0 => break,
+            _ if units.len() == max_len => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("String length exceeded limit ({})", max_len),
+                ))
+            }
+            unit => units.push(unit),
+        }
+    }
+    String::from_utf16(&units)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid UTF-16 string"))
+}
+
+/// Collapse runs of whitespace and control characters into a single space, trimming the ends.
+///
+/// Invisible format characters (bidi overrides, zero-width characters) are removed.
+pub fn sanitize(s: &str) -> String {
+    s.chars()
+        .filter(|&c| !is_format(c))
+        .collect::<String>()
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether the character is in the Unicode Cf (format) category.
+fn is_format(c: char) -> bool {
+    matches!(
+        c,
+        '\u{ad}'
+            | '\u{600}'..='\u{605}'
+            | '\u{61c}'
+            | '\u{6dd}'
+            | '\u{70f}'
+            | '\u{890}'..='\u{891}'
+            | '\u{8e2}'
+            | '\u{180e}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{206f}'
+            | '\u{feff}'
+            | '\u{fff9}'..='\u{fffb}'
+            | '\u{110bd}'
+            | '\u{110cd}'
+            | '\u{13430}'..='\u{1343f}'
+            | '\u{1bca0}'..='\u{1bca3}'
+            | '\u{1d173}'..='\u{1d17a}'
+            | '\u{e0001}'
+            | '\u{e0020}'..='\u{e007f}'
+    )
+}
+
+/// Extends the buffer to support putting MMO values.
+pub trait PutMMO: BufMut {
+    /// Put B value.
+    #[inline]
+    fn put_b(&mut self, buf: &[u8]) {
+        self.put_slice(buf)
+    }
+
+    /// Put C value (1 byte).
+    #[inline]
+    fn put_c(&mut self, n: i8) {
+        self.put_i8(n)
+    }
+
+    /// Put H value (2 bytes).
+    #[inline]
+    fn put_h(&mut self, n: i16) {
+        self.put_i16_le(n)
+    }
+
+    /// Put D value (4 bytes).
+    #[inline]
+    fn put_d(&mut self, n: i32) {
+        self.put_i32_le(n)
+    }
+}
+
+impl<T: BufMut> PutMMO for T {}
+
+/// Extends the buffer to support getting MMO values.
+pub trait GetMMO: Buf {
+    /// Get C value (1 byte).
+    #[inline]
+    fn get_c(&mut self) -> Result<i8> {
+        remaining(self, 1)?;
+        Ok(self.get_i8())
+    }
+
+    /// Get H value (2 bytes).
+    #[inline]
+    fn get_h(&mut self) -> Result<i16> {
+        remaining(self, 2)?;
+        Ok(self.get_i16_le())
+    }
+
+    /// Get D value (4 bytes).
+    #[inline]
+    fn get_d(&mut self) -> Result<i32> {
+        remaining(self, 4)?;
+        Ok(self.get_i32_le())
+    }
+}
+
+impl<T: Buf> GetMMO for T {}
+
+fn remaining<B: Buf + ?Sized>(buf: &B, size: usize) -> Result<()> {
+    if buf.remaining() < size {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("Buffer too short ({} < {})", buf.remaining(), size),
+        ));
+    }
+    Ok(())
+}
+
+/// Resize the buffer with zeros, wiping the old allocation if it has to move.
+pub fn resize_zeroed(buf: &mut BytesMut, len: usize) {
+    if len > buf.capacity() {
+        let mut grown = BytesMut::with_capacity(len.max(buf.capacity() * 2));
+        grown.extend_from_slice(buf);
+        wipe(buf);
+        *buf = grown;
+    }
+    buf.resize(len, 0);
+}
+
+/// Zero the whole allocation of the buffer, including the spare capacity, and clear it.
+pub fn wipe(buf: &mut BytesMut) {
+    buf.resize(buf.capacity(), 0);
+    buf.zeroize();
+    buf.clear();
+}
+
+/// Zero the initialized part of the buffer and clear it, leaving the spare capacity as is.
+///
+/// Covers everything written since the last clear, as long as the buffer only grew meanwhile.
+pub fn wipe_used(buf: &mut BytesMut) {
+    buf.as_mut().zeroize();
+    buf.clear();
+}
+
+/// Cursor over a buffer that grows with zeros on demand, up to the limit.
+pub struct BufCursor<'a> {
+    buf: &'a mut BytesMut,
+    position: usize,
+    limit: usize,
+}
+
+impl<'a> BufCursor<'a> {
+    /// Create a cursor at the start of the buffer.
+    pub fn new(buf: &'a mut BytesMut, limit: usize) -> Self {
+        Self {
+            buf,
+            position: 0,
+            limit,
+        }
+    }
+
+    /// Current position of the cursor.
+    pub fn position(&self) -> u64 {
+        self.position as u64
+    }
+}
+
+impl Write for BufCursor<'_> {
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        let end = self.position + data.len();
+        if end > self.limit {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Buffer size ({}) exceeded limit ({})", end, self.limit),
+            ));
+        }
+        if self.buf.len() < end {
+            resize_zeroed(self.buf, end);
+        }
+        self.buf[self.position..end].copy_from_slice(data);
+        self.position = end;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for BufCursor<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset as usize),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset as isize),
+            SeekFrom::End(offset) => self.buf.len().checked_add_signed(offset as isize),
+        };
+        self.position =
+            position.ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Invalid seek position"))?;
+        Ok(self.position as u64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
+    use bytes::BytesMut;
+
     use super::*;
 
     const BUFFER_SIZE: usize = 1024;
@@ -169,4 +405,380 @@ mod tests {
         assert_eq!(result.is_ok(), true);
         assert_eq!(result.unwrap(), 0x105C6A7B);
     }
+
+    #[test]
+    fn write_s() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+
+        // Act
+        let result = writer.write_s("abc");
+
+        // Assert
+        let position = writer.position() as usize;
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(position, 8);
+        assert_eq!(hex::encode(&buffer[..position]), "6100620063000000");
+    }
+
+    #[test]
+    fn write_s_non_bmp() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+
+        // Act
+        let result = writer.write_s("\u{1f600}");
+
+        // Assert
+        let position = writer.position() as usize;
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(position, 6);
+        assert_eq!(hex::encode(&buffer[..position]), "3dd800de0000");
+    }
+
+    #[test]
+    fn write_s_embedded_null() {
+        // Arrange
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut writer = Cursor::new(&mut buffer);
+
+        // Act
+        let result = writer.write_s("ab\0cd");
+
+        // Assert
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(writer.position(), 0);
+    }
+
+    #[test]
+    fn read_s() {
+        // Arrange
+        let buffer = hex::decode("6100620063000000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), "abc");
+    }
+
+    #[test]
+    fn read_s_korean() {
+        // Arrange
+        let buffer = hex::decode("5cd500ae0000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), "한글");
+    }
+
+    #[test]
+    fn read_s_russian() {
+        // Arrange
+        let buffer = hex::decode("1f04400438043204350442040000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), "Привет");
+    }
+
+    #[test]
+    fn read_s_non_bmp() {
+        // Arrange
+        let buffer = hex::decode("3dd800de0000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), "\u{1f600}");
+    }
+
+    #[test]
+    fn read_s_trailing_garbage() {
+        // Arrange
+        let buffer = hex::decode("610000007bff").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), "a");
+        assert_eq!(reader.position(), 4);
+    }
+
+    #[test]
+    fn read_s_unterminated() {
+        // Arrange
+        let buffer = hex::decode("61006200").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_s_invalid() {
+        // Arrange
+        let buffer = hex::decode("3dd861000000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s();
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Invalid UTF-16 string");
+    }
+
+    #[test]
+    fn read_s_limited() {
+        // Arrange
+        let buffer =
+            hex::decode("20006100090062000a000d001b0063000000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s_limited(16);
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), "a b c");
+    }
+
+    #[test]
+    fn read_s_limited_exceeded() {
+        // Arrange
+        let buffer = hex::decode("6100620063000000").expect("Failed to decode buffer");
+        let mut reader = Cursor::new(&buffer);
+
+        // Act
+        let result = reader.read_s_limited(2);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "String length exceeded limit (2)");
+    }
+
+    #[test]
+    fn sanitize_success() {
+        // Act
+        let result = sanitize("  admin\r\n[INFO] fake\u{7}  line\t");
+
+        // Assert
+        assert_eq!(result, "admin [INFO] fake line");
+    }
+
+    #[test]
+    fn sanitize_format() {
+        // Act
+        let result = sanitize("\u{feff}ad\u{200b}min \u{202e}txt.exe");
+
+        // Assert
+        assert_eq!(result, "admin txt.exe");
+    }
+
+    #[test]
+    fn put_b() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+
+        // Act
+        buffer.put_b(&[1, 2, 3]);
+
+        // Assert
+        assert_eq!(hex::encode(&buffer), "010203");
+    }
+
+    #[test]
+    fn put_c() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+
+        // Act
+        buffer.put_c(0x7b);
+
+        // Assert
+        assert_eq!(hex::encode(&buffer), "7b");
+    }
+
+    #[test]
+    fn put_h() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+
+        // Act
+        buffer.put_h(0x105c);
+
+        // Assert
+        assert_eq!(hex::encode(&buffer), "5c10");
+    }
+
+    #[test]
+    fn put_d() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+
+        // Act
+        buffer.put_d(0x105c6a7b);
+
+        // Assert
+        assert_eq!(hex::encode(&buffer), "7b6a5c10");
+    }
+
+    #[test]
+    fn get_c() {
+        // Arrange
+        let buffer = hex::decode("7b").expect("Failed to decode buffer");
+        let mut reader = &buffer[..];
+
+        // Act
+        let result = reader.get_c();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), 0x7b);
+    }
+
+    #[test]
+    fn get_h() {
+        // Arrange
+        let buffer = hex::decode("7b10").expect("Failed to decode buffer");
+        let mut reader = &buffer[..];
+
+        // Act
+        let result = reader.get_h();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), 0x107b);
+    }
+
+    #[test]
+    fn get_d() {
+        // Arrange
+        let buffer = hex::decode("7b6a5c10").expect("Failed to decode buffer");
+        let mut reader = &buffer[..];
+
+        // Act
+        let result = reader.get_d();
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), 0x105C6A7B);
+    }
+
+    #[test]
+    fn get_d_short() {
+        // Arrange
+        let buffer = hex::decode("7b6a5c").expect("Failed to decode buffer");
+        let mut reader = &buffer[..];
+
+        // Act
+        let result = reader.get_d();
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(reader.len(), 3);
+    }
+
+    #[test]
+    fn resize_zeroed_grow() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(2);
+        buffer.extend_from_slice(&[0x7b, 0x6a]);
+
+        // Act
+        resize_zeroed(&mut buffer, 4);
+
+        // Assert
+        assert_eq!(buffer.capacity() >= 4, true);
+        assert_eq!(hex::encode(&buffer), "7b6a0000");
+    }
+
+    #[test]
+    fn wipe_keeps_capacity() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+        buffer.extend_from_slice(&[0x7b, 0x6a]);
+
+        // Act
+        wipe(&mut buffer);
+
+        // Assert
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.capacity(), BUFFER_SIZE);
+    }
+
+    #[test]
+    fn wipe_used_keeps_capacity() {
+        // Arrange
+        let mut buffer = BytesMut::with_capacity(BUFFER_SIZE);
+        buffer.extend_from_slice(&[0x7b, 0x6a]);
+
+        // Act
+        wipe_used(&mut buffer);
+
+        // Assert
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.capacity(), BUFFER_SIZE);
+    }
+
+    #[test]
+    fn buf_cursor_grow() {
+        // Arrange
+        let mut buffer = BytesMut::new();
+        let mut writer = BufCursor::new(&mut buffer, BUFFER_SIZE);
+
+        // Act
+        let result = writer
+            .seek(SeekFrom::Current(2))
+            .and_then(|_| writer.write_d(0x105c6a7b));
+
+        // Assert
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(writer.position(), 6);
+        assert_eq!(hex::encode(&buffer), "00007b6a5c10");
+    }
+
+    #[test]
+    fn buf_cursor_limit() {
+        // Arrange
+        let mut buffer = BytesMut::new();
+        let mut writer = BufCursor::new(&mut buffer, 3);
+
+        // Act
+        let result = writer.write_d(0x105c6a7b);
+
+        // Assert
+        assert_eq!(result.is_err(), true);
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Buffer size (4) exceeded limit (3)");
+    }
 }
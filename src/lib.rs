@@ -1,6 +1,10 @@
 #![deny(missing_docs)]
 #![allow(dead_code)]
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
 //! Suite of tools for creating MMO servers.
 
 pub mod auth;
+#[cfg(test)]
+mod chaos;
+pub mod frame;
 pub mod io;